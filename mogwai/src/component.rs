@@ -50,6 +50,7 @@
 //! impl Component for App {
 //!   type ModelMsg = In;
 //!   type ViewMsg = Out;
+//!   type CommandMsg = ();
 //!
 //!   fn builder(&self, tx: Transmitter<In>, rx:Receiver<Out>) -> GizmoBuilder {
 //!     button()
@@ -63,7 +64,7 @@
 //!       }))
 //!   }
 //!
-//!   fn update(&mut self, msg: &In, tx_view: &Transmitter<Out>, _sub: &Subscriber<In>) {
+//!   fn update(&mut self, msg: &In, tx_view: &Transmitter<Out>, _tx_cmd: &Transmitter<FutureTask<()>>, _sub: &Subscriber<In>) {
 //!     match msg {
 //!       In::Click => {
 //!         self.num_clicks += 1;
@@ -101,7 +102,28 @@
 //!
 //! Components may be used within a [`GizmoBuilder`] using the
 //! [`GizmoBuilder::with`] function.
-use std::any::Any;
+//!
+//! ## Sharing context
+//!
+//! A component can stash a typed value on itself with
+//! [`GizmoComponent::provide_context`] and read it back with
+//! [`GizmoComponent::use_context`] - a place to keep something like a theme
+//! or a parsed config without inventing a dedicated message for it. This is
+//! local to the component that calls it; it is not a tree-wide lookup and
+//! does not reach ancestors or descendants.
+//!
+//! A tree-wide version (a child walking up through [`GizmoBuilder::with`]
+//! placements to read a value an ancestor provided) is not implemented here:
+//! `GizmoComponent` has no link back to whatever placed it, because nothing
+//! in this crate currently records that relationship when a component is
+//! placed into a builder. Wiring that up is a placement-tracking change to
+//! [`GizmoBuilder`] itself, not something `provide_context`/`use_context` can
+//! do on their own - until then, pass shared state explicitly (a message, or
+//! a context provided again on the child) across component boundaries.
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::ops::Deref;
 use wasm_bindgen::{JsCast, JsValue, UnwrapThrowExt};
@@ -114,6 +136,46 @@ use super::utils;
 pub mod subscriber;
 use subscriber::Subscriber;
 
+#[cfg(feature = "worker")]
+pub mod worker;
+
+
+/// A unit of async work submitted from [`Component::update`].
+///
+/// A `FutureTask` wraps a boxed, pinned future that resolves to a component's
+/// [`Component::CommandMsg`]. Submitting one on a component's `tx_cmd`
+/// transmitter spawns it with `wasm_bindgen_futures::spawn_local`; when the
+/// future resolves, its output is routed into [`Component::update_cmd`] under
+/// the same state lock as `update`.
+pub struct FutureTask<M>(Arc<Mutex<Option<Pin<Box<dyn Future<Output = M>>>>>>);
+
+
+impl<M> FutureTask<M> {
+  /// Wrap a future as a submittable command task.
+  pub fn new<F>(future: F) -> FutureTask<M>
+  where
+    F: Future<Output = M> + 'static,
+  {
+    FutureTask(Arc::new(Mutex::new(Some(Box::pin(future)))))
+  }
+
+  /// Take the wrapped future, if it hasn't already been taken.
+  fn take(&self) -> Option<Pin<Box<dyn Future<Output = M>>>> {
+    self
+      .0
+      .try_lock()
+      .expect("Could not get lock on FutureTask")
+      .take()
+  }
+}
+
+
+impl<M> Clone for FutureTask<M> {
+  fn clone(&self) -> Self {
+    FutureTask(self.0.clone())
+  }
+}
+
 
 /// Defines a component with distinct input (model update) and output
 /// (view update) messages.
@@ -134,18 +196,60 @@ where
   /// the view by being used in an rx_* function.
   type ViewMsg;
 
+  /// A command message is the output of an async [`FutureTask`] submitted from
+  /// `update` through `tx_cmd`. It is fed back into [`Component::update_cmd`]
+  /// once its future resolves.
+  ///
+  /// This is a breaking addition, not a backward-compatible default:
+  /// associated types can't default on stable Rust (the `= ()` syntax
+  /// requires the unstable `associated_type_defaults` feature, which this
+  /// crate doesn't enable), and `update` below also gained a required
+  /// `tx_cmd` parameter regardless. Every existing `Component` impl needs a
+  /// source change - add `type CommandMsg = ();` and the new `tx_cmd`
+  /// parameter (ignored as `_tx_cmd` if unused) - to keep compiling.
+  type CommandMsg: Clone;
+
   /// The type of DOM node that represents the root of this component.
   type DomNode;
 
   /// Update this component in response to any received model messages.
   /// This is essentially the component's fold function.
+  ///
+  /// Futures submitted on `tx_cmd` are spawned with
+  /// `wasm_bindgen_futures::spawn_local` and their output is routed into
+  /// [`Component::update_cmd`] once they resolve.
   fn update(
     &mut self,
     msg: &Self::ModelMsg,
     tx_view: &Transmitter<Self::ViewMsg>,
+    tx_cmd: &Transmitter<FutureTask<Self::CommandMsg>>,
     sub: &Subscriber<Self::ModelMsg>,
   );
 
+  /// Update this component in response to a resolved [`Self::CommandMsg`]
+  /// produced by a future submitted on `tx_cmd` from [`Component::update`].
+  ///
+  /// The default implementation does nothing, so components that don't use
+  /// async commands don't need to implement this.
+  fn update_cmd(
+    &mut self,
+    _msg: &Self::CommandMsg,
+    _tx_view: &Transmitter<Self::ViewMsg>,
+    _tx_cmd: &Transmitter<FutureTask<Self::CommandMsg>>,
+    _sub: &Subscriber<Self::ModelMsg>,
+  ) {
+  }
+
+  /// Called once when this component is detached, either because its
+  /// [`GizmoComponent`] was dropped or because
+  /// [`GizmoComponent::unmount`] was called explicitly. Use this to cancel
+  /// in-flight timers, close sockets, or drop other subscriptions this
+  /// component holds.
+  ///
+  /// The default implementation does nothing.
+  fn unmount(&mut self, _tx_view: &Transmitter<Self::ViewMsg>) {
+  }
+
   /// Produce this component's gizmo using inputs and outputs.
   fn view(
     &self,
@@ -158,6 +262,18 @@ where
   fn into_component(self) -> GizmoComponent<Self> {
     GizmoComponent::new(self)
   }
+
+  /// Helper function for moving this component's `update` fold onto a
+  /// dedicated Web Worker. See the [`worker`] module for details.
+  #[cfg(feature = "worker")]
+  fn into_worker_component(self, worker_script_url: &str) -> worker::WorkerComponent<Self>
+  where
+    Self: serde::Serialize + serde::de::DeserializeOwned,
+    Self::ModelMsg: serde::Serialize + serde::de::DeserializeOwned,
+    Self::ViewMsg: serde::Serialize + serde::de::DeserializeOwned,
+  {
+    worker::WorkerComponent::new(self, worker_script_url)
+  }
 }
 
 
@@ -199,9 +315,81 @@ pub struct GizmoComponent<T: Component> {
 
   pub(crate) gizmo: Gizmo<T::DomNode>,
   pub(crate) state: Arc<Mutex<T>>,
+  pub(crate) tx_view: Transmitter<T::ViewMsg>,
+  pub(crate) live: Arc<Mutex<bool>>,
+  pub(crate) contexts: Arc<Mutex<HashMap<TypeId, Arc<dyn Any>>>>,
+
+  // Runs `Component::unmount` when this `GizmoComponent` is dropped. Pulled
+  // out into its own field rather than implementing `Drop` directly on
+  // `GizmoComponent`, because `From<T> for Gizmo<D>` moves `gizmo` out of a
+  // `GizmoComponent` by value, and Rust forbids partially moving out of a
+  // type that itself implements `Drop` (E0509).
+  _teardown: GizmoComponentTeardown<T>,
 }
 
 
+/// Holds just the pieces [`Component::unmount`] needs, so that its `Drop`
+/// impl can run teardown automatically without making `GizmoComponent`
+/// itself a `Drop` type. See the `_teardown` field above.
+struct GizmoComponentTeardown<T: Component> {
+  state: Arc<Mutex<T>>,
+  tx_view: Transmitter<T::ViewMsg>,
+  live: Arc<Mutex<bool>>,
+}
+
+
+impl<T: Component> Drop for GizmoComponentTeardown<T> {
+  fn drop(&mut self) {
+    {
+      let live =
+        self
+        .live
+        .try_lock()
+        .expect("Could not get lock on GizmoComponent live flag");
+      if !*live {
+        return;
+      }
+    }
+    {
+      let mut t =
+        self
+        .state
+        .try_lock()
+        .expect("Could not get lock on GizmoComponent state");
+      T::unmount(&mut t, &self.tx_view);
+    }
+    *self
+      .live
+      .try_lock()
+      .expect("Could not get lock on GizmoComponent live flag") = false;
+  }
+}
+
+
+impl<T: Component> GizmoComponent<T> {
+  /// Take ownership of this component's `Gizmo` without running
+  /// `_teardown`'s `Drop`.
+  ///
+  /// `run` and `into_sub_gizmo` only want the `Gizmo` out of `self`, and in
+  /// both cases the component is meant to keep reacting to messages
+  /// afterward - running forever, or living on as a child in a parent's
+  /// tree - not to be torn down. Since the rest of `self` (trns, recv,
+  /// state, `_teardown`, ...) is otherwise dropped the instant such a
+  /// `self`-consuming method returns, calling it would flip `live` to
+  /// `false` and silently turn every responder into a no-op right after the
+  /// component starts. `self`'s responders hold their own clones of its
+  /// `Arc` state already, so leaking the handle here costs nothing; only an
+  /// explicit [`GizmoComponent::unmount`] call, or dropping a
+  /// `GizmoComponent` that was never `run` or placed, should trigger
+  /// teardown.
+  fn take_gizmo(self) -> Gizmo<T::DomNode> {
+    let this = std::mem::ManuallyDrop::new(self);
+    unsafe { std::ptr::read(&this.gizmo) }
+  }
+}
+
+
+
 impl<T:Component> Deref for GizmoComponent<T> {
   type Target = Gizmo<T::DomNode>;
 
@@ -225,60 +413,115 @@ where
     let subscriber = Subscriber::new(&tx_in);
 
     let (tx_view, rx_view) = txrx();
-    rx_in.respond(move |msg: &T::ModelMsg| {
-      let mut t =
-        state
-        .try_lock()
-        .expect("Could not get component state lock");
-      T::update(&mut t, msg, &tx_view, &subscriber);
-    });
-
-    let out_msgs = Arc::new(Mutex::new(vec![]));
-    rx_view.respond(move |msg: &T::ViewMsg| {
-      let should_schedule =
-        {
-        let mut msgs =
-          out_msgs
+    let (tx_cmd, rx_cmd) = txrx();
+    let live = Arc::new(Mutex::new(true));
+
+    {
+      let state = state.clone();
+      let tx_view = tx_view.clone();
+      let tx_cmd = tx_cmd.clone();
+      let live = live.clone();
+      rx_in.respond(move |msg: &T::ModelMsg| {
+        if !*live.try_lock().expect("Could not get component live lock") {
+          return;
+        }
+        let mut t =
+          state
           .try_lock()
-          .expect("Could not try_lock to push to out_msgs");
-        msgs.push(msg.clone());
-        // If there is more than just this message in the queue, this
-        // responder has already been run this frame and a timer has
-        // already been scheduled, so there's no need to schedule another
-        msgs.len() == 1
-      };
-      if should_schedule {
-        let out_msgs_async = out_msgs.clone();
-        let tx_out_async = tx_out.clone();
-        utils::timeout(0, move || {
-          let msgs =
-            {
-            out_msgs_async
+          .expect("Could not get component state lock");
+        T::update(&mut t, msg, &tx_view, &tx_cmd, &subscriber);
+      });
+    }
+
+    {
+      let state = state.clone();
+      let tx_view = tx_view.clone();
+      let tx_cmd = tx_cmd.clone();
+      let tx_in = tx_in.clone();
+      let live = live.clone();
+      rx_cmd.respond(move |task: &FutureTask<T::CommandMsg>| {
+        if !*live.try_lock().expect("Could not get component live lock") {
+          return;
+        }
+        if let Some(future) = task.take() {
+          let state = state.clone();
+          let tx_view = tx_view.clone();
+          let tx_cmd = tx_cmd.clone();
+          let sub = Subscriber::new(&tx_in);
+          wasm_bindgen_futures::spawn_local(async move {
+            let cmd_msg = future.await;
+            let mut t =
+              state
               .try_lock()
-              .expect("Could not try_lock to pop out_msgs")
-              .drain(0..)
-              .collect::<Vec<_>>()
-          };
-          if msgs.len() > 0 {
-            msgs.iter().for_each(|out_msg| {
-              tx_out_async.send(out_msg);
-            });
-          }
-          false
-        });
-      }
-    });
+              .expect("Could not get component state lock");
+            T::update_cmd(&mut t, &cmd_msg, &tx_view, &tx_cmd, &sub);
+          });
+        }
+      });
+    }
+
+    let out_msgs = Arc::new(Mutex::new(vec![]));
+    {
+      let live = live.clone();
+      rx_view.respond(move |msg: &T::ViewMsg| {
+        if !*live.try_lock().expect("Could not get component live lock") {
+          return;
+        }
+        let should_schedule =
+          {
+          let mut msgs =
+            out_msgs
+            .try_lock()
+            .expect("Could not try_lock to push to out_msgs");
+          msgs.push(msg.clone());
+          // If there is more than just this message in the queue, this
+          // responder has already been run this frame and a timer has
+          // already been scheduled, so there's no need to schedule another
+          msgs.len() == 1
+        };
+        if should_schedule {
+          let out_msgs_async = out_msgs.clone();
+          let tx_out_async = tx_out.clone();
+          utils::timeout(0, move || {
+            let msgs =
+              {
+              out_msgs_async
+                .try_lock()
+                .expect("Could not try_lock to pop out_msgs")
+                .drain(0..)
+                .collect::<Vec<_>>()
+            };
+            if msgs.len() > 0 {
+              msgs.iter().for_each(|out_msg| {
+                tx_out_async.send(out_msg);
+              });
+            }
+            false
+          });
+        }
+      });
+    }
 
     let gizmo = {
       let component = component_var.try_lock().unwrap_throw();
       component.view(tx_in.clone(), rx_out.branch())
     };
 
+    let _teardown = GizmoComponentTeardown {
+      state: component_var.clone(),
+      tx_view: tx_view.clone(),
+      live: live.clone(),
+    };
+
     GizmoComponent {
       trns: tx_in,
       recv: rx_out,
       gizmo,
       state: component_var,
+      tx_view,
+      live,
+      contexts: Arc::new(Mutex::new(HashMap::new())),
+      _teardown,
     }
   }
 
@@ -309,6 +552,47 @@ where
     self
   }
 
+  /// Forward this component's view messages into `tx`, transforming each one
+  /// with `f` and dropping it whenever `f` returns `None`.
+  ///
+  /// Unlike [`GizmoComponent::tx_into`], this lets a parent adapt a child's
+  /// `ViewMsg`s into its own message vocabulary instead of requiring both
+  /// sides to share a message type.
+  pub fn forward_output<X, F>(self, tx: &Transmitter<X>, f: F) -> GizmoComponent<T>
+  where
+    X: Clone + 'static,
+    F: Fn(&T::ViewMsg) -> Option<X> + 'static,
+  {
+    let tx = tx.clone();
+    self.recv.branch().respond(move |msg: &T::ViewMsg| {
+      if let Some(out) = f(msg) {
+        tx.send(&out);
+      }
+    });
+    self
+  }
+
+  /// Forward messages from `rx` into this component's model messages,
+  /// transforming each one with `f` and dropping it whenever `f` returns
+  /// `None`.
+  ///
+  /// Unlike [`GizmoComponent::rx_from`], this lets a parent feed messages of
+  /// another type into this component's `ModelMsg` vocabulary instead of
+  /// requiring both sides to share a message type.
+  pub fn forward_input<X, F>(self, rx: Receiver<X>, f: F) -> GizmoComponent<T>
+  where
+    X: Clone + 'static,
+    F: Fn(&X) -> Option<T::ModelMsg> + 'static,
+  {
+    let trns = self.trns.clone();
+    rx.respond(move |msg: &X| {
+      if let Some(input) = f(msg) {
+        trns.send(&input);
+      }
+    });
+    self
+  }
+
   /// Run and initialize the component with a list of messages.
   /// This is equivalent to calling `run` and `update` with each message.
   pub fn run_init(mut self, msgs: Vec<T::ModelMsg>) -> Result<(), JsValue> {
@@ -318,9 +602,50 @@ where
     self.run()
   }
 
-  /// Run this component forever
+  /// Run this component forever.
+  ///
+  /// This consumes `self`, but the component keeps reacting to messages
+  /// from the DOM (and from a parent calling `update` on a clone of
+  /// `trns`) exactly as before - `run` only hands the `Gizmo` off to the
+  /// page, it does not tear the component down. Contrast with
+  /// [`GizmoComponent::unmount`], which does.
+  ///
+  /// ```rust, no_run
+  /// extern crate mogwai;
+  /// use mogwai::prelude::*;
+  ///
+  /// #[derive(Clone)]
+  /// struct Msg;
+  ///
+  /// struct App;
+  ///
+  /// impl Component for App {
+  ///   type ModelMsg = Msg;
+  ///   type ViewMsg = Msg;
+  ///   type CommandMsg = ();
+  ///   type DomNode = web_sys::HtmlElement;
+  ///
+  ///   fn update(&mut self, _msg: &Msg, tx_view: &Transmitter<Msg>, _tx_cmd: &Transmitter<FutureTask<()>>, _sub: &Subscriber<Msg>) {
+  ///     tx_view.send(&Msg);
+  ///   }
+  ///
+  ///   fn view(&self, _tx: Transmitter<Msg>, rx: Receiver<Msg>) -> Gizmo<web_sys::HtmlElement> {
+  ///     div().rx_text("", rx.branch_map(|_| "got it".to_string()))
+  ///   }
+  /// }
+  ///
+  /// pub fn main() -> Result<(), JsValue> {
+  ///   let component = App.into_component();
+  ///   let trns = component.trns.clone();
+  ///   // `run` must not silently disable the component's responders - a
+  ///   // message sent afterward still has to reach `update`.
+  ///   component.run()?;
+  ///   trns.send(&Msg);
+  ///   Ok(())
+  /// }
+  /// ```
   pub fn run(self) -> Result<(), JsValue> {
-    self.gizmo.run()
+    self.take_gizmo().run()
   }
 
   /// Append this component's gizmo an HtmlElement.
@@ -349,6 +674,143 @@ where
       .expect("Could not get lock on GizmoComponent state");
     f(&t)
   }
+
+  /// Detach this component: run its [`Component::unmount`] hook under the
+  /// state lock, then stop its internal responders from firing. This is
+  /// called automatically when the `GizmoComponent` is dropped, but may
+  /// also be called explicitly to tear a component down early. Calling it
+  /// more than once has no additional effect.
+  pub fn unmount(&mut self) {
+    {
+      let live =
+        self
+        .live
+        .try_lock()
+        .expect("Could not get lock on GizmoComponent live flag");
+      if !*live {
+        return;
+      }
+    }
+    {
+      let mut t =
+        self
+        .state
+        .try_lock()
+        .expect("Could not get lock on GizmoComponent state");
+      T::unmount(&mut t, &self.tx_view);
+    }
+    *self
+      .live
+      .try_lock()
+      .expect("Could not get lock on GizmoComponent live flag") = false;
+  }
+
+  /// Provide a value of type `C` on this component, for retrieval with
+  /// [`GizmoComponent::use_context`]. This is a typed side-channel for a
+  /// single component's own state (e.g. something it reads in `view` or
+  /// `update`), not a tree-wide lookup - it does not reach ancestors or
+  /// descendants.
+  pub fn provide_context<C: Any>(&self, ctx: C) {
+    self
+      .contexts
+      .try_lock()
+      .expect("Could not get lock on GizmoComponent contexts")
+      .insert(TypeId::of::<C>(), Arc::new(ctx));
+  }
+
+  /// Look up a context value of type `C` previously provided on this
+  /// component with [`GizmoComponent::provide_context`]. Returns `None` if
+  /// this component hasn't provided a `C`.
+  ///
+  /// This only ever looks at `self` - see the module-level "Sharing
+  /// context" section for why an ancestor-walking version isn't offered
+  /// here.
+  pub fn use_context<C: Any + Clone>(&self) -> Option<C> {
+    self
+      .contexts
+      .try_lock()
+      .expect("Could not get lock on GizmoComponent contexts")
+      .get(&TypeId::of::<C>())
+      .and_then(|ctx| ctx.downcast_ref::<C>().cloned())
+  }
+
+  /// Register this component's model message transmitter with `broker`, so
+  /// that any message sent with [`MessageBroker::send`] is also delivered
+  /// to this component's `update`.
+  pub fn connect_broker(&self, broker: &MessageBroker<T::ModelMsg>) {
+    broker.subscribe_transmitter(self.trns.clone());
+  }
+}
+
+
+/// A fan-out message bus for addressing components (or any other listener)
+/// by handle, without holding a concrete `Transmitter` reference to them.
+///
+/// Components subscribe with [`GizmoComponent::connect_broker`]; other code
+/// can subscribe with [`MessageBroker::subscribe`]. Sending a message with
+/// [`MessageBroker::send`] fans it out to every current subscriber, which is
+/// handy for app-wide events like a "logout" or "route changed" signal.
+pub struct MessageBroker<M: Clone> {
+  subscribers: Arc<Mutex<Vec<Transmitter<M>>>>,
+}
+
+
+impl<M: Clone + 'static> MessageBroker<M> {
+  /// Create an empty broker with no subscribers yet.
+  pub fn new() -> MessageBroker<M> {
+    MessageBroker {
+      subscribers: Arc::new(Mutex::new(vec![])),
+    }
+  }
+
+  /// Send a message to every current subscriber.
+  ///
+  /// The subscriber list is cloned out from under the lock before sending,
+  /// the same way `GizmoComponent::new`'s `out_msgs` responder drains its
+  /// queue before calling into any transmitter - so a subscriber that reacts
+  /// to this message by subscribing or connecting to the same broker (e.g.
+  /// spawning a component in response to a broadcast) doesn't deadlock on
+  /// `subscribers`.
+  pub fn send(&self, msg: &M) {
+    let subscribers =
+      self
+      .subscribers
+      .try_lock()
+      .expect("Could not get lock on MessageBroker subscribers")
+      .clone();
+    subscribers.iter().for_each(|tx| tx.send(msg));
+  }
+
+  /// Subscribe to this broker's message stream from outside a component.
+  pub fn subscribe(&self) -> Receiver<M> {
+    let (tx, rx) = txrx();
+    self.subscribe_transmitter(tx);
+    rx
+  }
+
+  fn subscribe_transmitter(&self, tx: Transmitter<M>) {
+    self
+      .subscribers
+      .try_lock()
+      .expect("Could not get lock on MessageBroker subscribers")
+      .push(tx);
+  }
+}
+
+
+impl<M: Clone + 'static> Default for MessageBroker<M> {
+  fn default() -> Self {
+    MessageBroker::new()
+  }
+}
+
+
+impl<M: Clone> Clone for MessageBroker<M> {
+  fn clone(&self) -> Self {
+    MessageBroker {
+      subscribers: self.subscribers.clone(),
+    }
+  }
 }
 
 
@@ -358,7 +820,7 @@ where
   T::DomNode: AsRef<Node>
 {
   fn into_sub_gizmo(self) -> Result<Gizmo<Node>, Node> {
-    self.gizmo.into_sub_gizmo()
+    self.take_gizmo().into_sub_gizmo()
   }
 }
 
@@ -398,12 +860,14 @@ where
 {
   type ModelMsg = T;
   type ViewMsg = T;
+  type CommandMsg = ();
   type DomNode = D;
 
   fn update(
     &mut self,
     msg: &T,
     tx_view: &Transmitter<T>,
+    _tx_cmd: &Transmitter<FutureTask<()>>,
     _sub: &Subscriber<T>,
   ) {
     tx_view.send(msg);
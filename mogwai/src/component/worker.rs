@@ -0,0 +1,195 @@
+//! Web-Worker-backed components.
+//!
+//! Gated behind the `worker` feature. A [`WorkerComponent`] mirrors
+//! [`Component`] but runs its `update` fold inside a dedicated Web Worker
+//! instead of on the main thread, so heavy model computation (parsing,
+//! crypto, big folds) can't block DOM updates the way the single-threaded
+//! `Arc<Mutex<T>>` design in [`GizmoComponent`](super::GizmoComponent)
+//! otherwise would.
+//!
+//! `WorkerComponent` is a separate, minimal struct - it does not carry over
+//! [`GizmoComponent`](super::GizmoComponent)'s `unmount`, context, broker, or
+//! `forward_output`/`forward_input` support. Moving a component to the
+//! worker variant means giving those up for now.
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, Node, Worker};
+
+use super::Component;
+use crate::gizmo::Gizmo;
+use crate::txrx::{txrx, Receiver, Transmitter};
+use crate::utils;
+
+/// A message posted over the `postMessage` bridge between a
+/// [`WorkerComponent`] and its Web Worker. `T` is the whole component state,
+/// sent once as `Init`; `M` is the message type of whichever direction is
+/// being carried (`T::ModelMsg` going in, `T::ViewMsg` coming back), sent
+/// repeatedly as `Update`. Both variants are always instantiated through the
+/// same `HostMsg<T, M>` so there is only ever one concrete type per
+/// direction for the worker-side code to match against.
+#[derive(Serialize, Deserialize)]
+enum HostMsg<T, M> {
+  Init(T),
+  Update(M),
+}
+
+/// A component whose [`Component::update`] fold runs on a dedicated Web
+/// Worker instead of the main thread.
+///
+/// `ModelMsg`s sent on [`WorkerComponent::trns`] are serialized and posted
+/// to the worker, where the real `update` runs against the component's
+/// state. The worker posts back serialized `ViewMsg`s, which are
+/// deserialized here and batched the same way
+/// [`GizmoComponent::new`](super::GizmoComponent::new) batches `rx_view`
+/// into `tx_out`: each message is queued and a single `0`ms timeout drains
+/// the queue into [`WorkerComponent::recv`], so a burst of worker messages
+/// in one frame becomes one round of view updates instead of one per
+/// message.
+///
+/// The view itself is still built on the main thread: [`Component::view`]
+/// only needs a `&self`, so [`WorkerComponent::new`] calls it on `init`
+/// before moving `init` off to the worker, and keeps the resulting
+/// [`Gizmo`] so this type can actually be placed in (or run as) a page,
+/// the same way [`GizmoComponent`](super::GizmoComponent) does.
+pub struct WorkerComponent<T: Component> {
+  pub trns: Transmitter<T::ModelMsg>,
+  pub recv: Receiver<T::ViewMsg>,
+
+  gizmo: Gizmo<T::DomNode>,
+  worker: Worker,
+  _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl<T> WorkerComponent<T>
+where
+  T: Component + Serialize + DeserializeOwned + 'static,
+  T::ModelMsg: Serialize + DeserializeOwned,
+  T::ViewMsg: Serialize + DeserializeOwned,
+{
+  /// Spawn `init` onto a dedicated Web Worker loaded from `worker_script_url`
+  /// and wire up the `postMessage` bridge that ferries model messages in and
+  /// view messages back out.
+  ///
+  /// The worker script is expected to run the same component's `update` fold
+  /// in response to `HostMsg::Update(T::ModelMsg)` messages and post back
+  /// `HostMsg::Update(T::ViewMsg)` messages. Every message in both
+  /// directions is a JSON string. This crate does not provide the
+  /// worker-side entry point; the worker script has to decode and encode
+  /// those messages itself.
+  pub fn new(init: T, worker_script_url: &str) -> WorkerComponent<T> {
+    let worker =
+      Worker::new(worker_script_url)
+      .expect("Could not create Web Worker for WorkerComponent");
+
+    let (tx_in, rx_in) = txrx();
+    let (tx_out, rx_out) = txrx();
+
+    // `view` only borrows `init`, so build the Gizmo before handing `init`
+    // off to the worker below.
+    let gizmo = init.view(tx_in.clone(), rx_out.branch());
+
+    let init_msg =
+      serde_json::to_string(&HostMsg::<T, T::ModelMsg>::Init(init))
+      .expect("Could not serialize WorkerComponent init state");
+    worker
+      .post_message(&JsValue::from_str(&init_msg))
+      .expect("Could not post init message to Web Worker");
+
+    let out_msgs = Arc::new(Mutex::new(vec![]));
+    let on_message = Closure::wrap(Box::new(move |ev: MessageEvent| {
+      let text =
+        ev
+        .data()
+        .as_string()
+        .expect("WorkerComponent received a non-string message");
+      let msg: T::ViewMsg = match serde_json::from_str::<HostMsg<T, T::ViewMsg>>(&text)
+        .expect("Could not deserialize ViewMsg from Web Worker")
+      {
+        HostMsg::Update(msg) => msg,
+        HostMsg::Init(_) => return,
+      };
+
+      let should_schedule = {
+        let mut msgs =
+          out_msgs
+          .try_lock()
+          .expect("Could not try_lock to push to out_msgs");
+        msgs.push(msg);
+        // Like `GizmoComponent::new`'s `out_msgs` responder: if there's more
+        // than this message in the queue, a timeout has already been
+        // scheduled to drain it, so there's no need to schedule another.
+        msgs.len() == 1
+      };
+      if should_schedule {
+        let out_msgs_async = out_msgs.clone();
+        let tx_out_async = tx_out.clone();
+        utils::timeout(0, move || {
+          let msgs =
+            out_msgs_async
+            .try_lock()
+            .expect("Could not try_lock to pop out_msgs")
+            .drain(0..)
+            .collect::<Vec<_>>();
+          msgs.iter().for_each(|msg| {
+            tx_out_async.send(msg);
+          });
+          false
+        });
+      }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+    let worker_for_updates = worker.clone();
+    rx_in.respond(move |msg: &T::ModelMsg| {
+      let update_msg =
+        serde_json::to_string(&HostMsg::<T, T::ModelMsg>::Update(msg.clone()))
+        .expect("Could not serialize ModelMsg for Web Worker");
+      worker_for_updates
+        .post_message(&JsValue::from_str(&update_msg))
+        .expect("Could not post ModelMsg to Web Worker");
+    });
+
+    WorkerComponent {
+      trns: tx_in,
+      recv: rx_out,
+      gizmo,
+      worker,
+      _on_message: on_message,
+    }
+  }
+
+  /// Send a model message into the worker.
+  pub fn update(&self, msg: &T::ModelMsg) {
+    self.trns.send(msg);
+  }
+
+  /// A reference to the Gizmo.
+  pub fn gizmo_ref(&self) -> &Gizmo<T::DomNode> {
+    &self.gizmo
+  }
+
+  /// A reference to the DomNode.
+  pub fn dom_ref(&self) -> &T::DomNode {
+    self.gizmo.element.unchecked_ref()
+  }
+
+  /// Append this component's gizmo to an HtmlElement.
+  pub fn append_to(&self, parent: &Node) {
+    parent
+      .append_child(self.gizmo.as_ref())
+      .expect("could not append component to parent node");
+  }
+
+  /// Run this component forever.
+  pub fn run(self) -> Result<(), JsValue> {
+    self.gizmo.run()
+  }
+
+  /// Terminate the backing Web Worker.
+  pub fn terminate(&self) {
+    self.worker.terminate();
+  }
+}